@@ -0,0 +1,55 @@
+use std::io::{self, BufRead};
+
+/// The result of decoding a chunked-transfer-encoded body: the reassembled
+/// payload plus any trailer headers sent after the terminating chunk.
+pub struct ChunkedBody {
+    pub body: String,
+    pub trailers: Vec<String>,
+}
+
+/// Reads a `Transfer-Encoding: chunked` body per RFC 9112 §7.1.
+///
+/// Each chunk is a size line (chunk extensions after a `;` are discarded),
+/// that many bytes of data, and a trailing CRLF; decoding loops until a
+/// zero-size chunk, after which trailer header lines are read up to the
+/// final blank line. Mirrors the shape of `http_io`'s `HttpChunkedBody`.
+pub fn read_chunked_body<R: BufRead>(reader: &mut R) -> io::Result<ChunkedBody> {
+    let mut body = String::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size_token = line.trim().split(';').next().unwrap_or("").trim();
+        let chunk_size = usize::from_str_radix(size_token, 16).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid chunk size {size_token:?}: {e}"),
+            )
+        })?;
+
+        if chunk_size == 0 {
+            break;
+        }
+
+        let mut chunk = vec![0; chunk_size];
+        reader.read_exact(&mut chunk)?;
+        body.push_str(&String::from_utf8_lossy(&chunk));
+
+        // Consume the CRLF that terminates the chunk data.
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+
+    let mut trailers = Vec::new();
+    loop {
+        line.clear();
+        let bytes_read = reader.read_line(&mut line)?;
+        if bytes_read == 0 || line == "\r\n" {
+            break;
+        }
+        trailers.push(line.trim().to_string());
+    }
+
+    Ok(ChunkedBody { body, trailers })
+}
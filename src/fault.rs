@@ -0,0 +1,84 @@
+/// A minimal xorshift64 PRNG. Using a tiny, self-contained generator
+/// instead of an external crate keeps `--drop-rate`/`--fault-rate` draws
+/// reproducible for a given `--seed`.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng {
+            state: seed.max(1),
+        }
+    }
+
+    /// Returns a uniformly distributed value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Mixes the base seed with a connection id and a draw-purpose tag
+/// (splitmix64-style) into a single seed. Keying each draw off the
+/// connection id rather than a shared RNG means `--workers N` can schedule
+/// connections in whatever order it likes — the drop/fault outcome for a
+/// given connection only depends on `--seed` and that connection's id, not
+/// which worker thread happens to pick it up or when.
+fn mix(seed: u64, connection_id: u64, purpose: u64) -> u64 {
+    let mut x = seed
+        .wrapping_add(connection_id.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        .wrapping_add(purpose.wrapping_mul(0xBF58_476D_1CE4_E5B9));
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+const DROP_DRAW: u64 = 1;
+const FAULT_DRAW: u64 = 2;
+
+/// Deliberate latency and fault injection, so `waiter` can stand in for a
+/// flaky dependency: `delay_ms` simulates a slow upstream, `drop_rate`
+/// simulates a connection that never replies, and `fault_code`/`fault_rate`
+/// simulate a dependency that intermittently returns a 5xx.
+pub struct FaultConfig {
+    pub delay_ms: u64,
+    drop_rate: f64,
+    fault_code: Option<u16>,
+    fault_rate: f64,
+    seed: u64,
+}
+
+impl FaultConfig {
+    pub fn new(delay_ms: u64, drop_rate: f64, fault_code: Option<u16>, fault_rate: f64, seed: u64) -> FaultConfig {
+        FaultConfig {
+            delay_ms,
+            drop_rate,
+            fault_code,
+            fault_rate,
+            seed,
+        }
+    }
+
+    fn roll(&self, connection_id: u64, purpose: u64) -> f64 {
+        Rng::new(mix(self.seed, connection_id, purpose)).next_f64()
+    }
+
+    /// Whether `connection_id` should be silently dropped per `--drop-rate`.
+    pub fn should_drop(&self, connection_id: u64) -> bool {
+        self.drop_rate > 0.0 && self.roll(connection_id, DROP_DRAW) < self.drop_rate
+    }
+
+    /// Returns `--fault-code` if `connection_id` should be faulted per
+    /// `--fault-rate`.
+    pub fn fault_code(&self, connection_id: u64) -> Option<u16> {
+        match self.fault_code {
+            Some(code) if self.fault_rate > 0.0 && self.roll(connection_id, FAULT_DRAW) < self.fault_rate => {
+                Some(code)
+            }
+            _ => None,
+        }
+    }
+}
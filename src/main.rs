@@ -1,10 +1,42 @@
 use std::{
+    fs,
     io::{BufReader, prelude::*},
-    net::{TcpListener, TcpStream},
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use clap::Parser;
 
+mod chunked;
+mod fault;
+mod pool;
+mod record;
+mod rules;
+mod tls;
+
+use fault::FaultConfig;
+use pool::ThreadPool;
+use rules::{Rule, RequestLine};
+
+/// The response to send when no rule matches the incoming request, built
+/// from `--body`/`--body-file`, `--header` and `--content-type`.
+struct DefaultResponse {
+    status: HttpStatusCode,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// Parses a `key:value` CLI header argument.
+fn parse_header_arg(raw: &str) -> Result<(String, String), String> {
+    let (name, value) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("header {raw:?} is not in key:value form"))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
 #[derive(Parser)]
 #[command(name = "serve")]
 #[command(version = "0.0.1")]
@@ -16,6 +48,53 @@ struct Arguments {
     port: u16,
     #[arg(long, default_value = "200")]
     return_code: u16,
+    /// Path to a JSON or TOML file of rules matched against method/path/headers.
+    /// The first matching rule's status/headers/body is returned; if nothing
+    /// matches, `--return-code` is used instead.
+    #[arg(long)]
+    rules: Option<PathBuf>,
+    /// Literal response body for unmatched requests.
+    #[arg(long, conflicts_with = "body_file")]
+    body: Option<String>,
+    /// Path to a file whose contents are used as the response body for unmatched requests.
+    #[arg(long)]
+    body_file: Option<PathBuf>,
+    /// Extra response header for unmatched requests, in `key:value` form; may be passed multiple times.
+    #[arg(long = "header", value_name = "KEY:VALUE")]
+    headers: Vec<String>,
+    /// Value for the response `Content-Type` header for unmatched requests.
+    #[arg(long)]
+    content_type: Option<String>,
+    /// Number of worker threads handling connections concurrently.
+    #[arg(long, default_value = "4")]
+    workers: usize,
+    /// Path to a PEM certificate to terminate TLS with (requires --tls-key).
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// Path to a PEM private key to terminate TLS with (requires --tls-cert).
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Terminate TLS with a freshly generated, ephemeral self-signed certificate.
+    #[arg(long, conflicts_with_all = ["tls_cert", "tls_key"])]
+    tls_self_signed: bool,
+    /// Append one JSON object per received request to this file.
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Milliseconds to sleep before sending the response, to simulate a slow upstream.
+    #[arg(long, default_value = "0")]
+    delay_ms: u64,
+    /// Fraction (0.0-1.0) of connections to silently close without replying.
+    #[arg(long, default_value = "0.0")]
+    drop_rate: f64,
+    /// Status code to substitute for a fraction of responses (use with --fault-rate).
+    #[arg(long)]
+    fault_code: Option<u16>,
+    /// Fraction (0.0-1.0) of requests that get --fault-code instead of their normal response.
+    #[arg(long, default_value = "0.0")]
+    fault_rate: f64,
+    /// Seed for the deterministic RNG driving --drop-rate/--fault-rate, so runs are reproducible.
+    #[arg(long, default_value = "42")]
+    seed: u64,
 }
 
 fn main() {
@@ -25,6 +104,50 @@ fn main() {
           Some(t) => t,
           None => panic!("Invalid return code {}",args.return_code)
     };
+    let rules = match &args.rules {
+        Some(path) => match rules::load_rules(path) {
+            Ok(rules) => rules,
+            Err(e) => panic!("Could not load rules from {path:?}: {e}"),
+        },
+        None => Vec::new(),
+    };
+    let body = match (&args.body, &args.body_file) {
+        (Some(body), _) => body.clone().into_bytes(),
+        (None, Some(path)) => {
+            fs::read(path).unwrap_or_else(|e| panic!("Could not read body file {path:?}: {e}"))
+        }
+        (None, None) => Vec::new(),
+    };
+    let mut response_headers = args
+        .headers
+        .iter()
+        .map(|header| parse_header_arg(header))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| panic!("{e}"));
+    if let Some(content_type) = &args.content_type {
+        response_headers.push(("Content-Type".to_string(), content_type.clone()));
+    }
+    let default_response = Arc::new(DefaultResponse {
+        status: http_response_code,
+        headers: response_headers,
+        body,
+    });
+    let rules = Arc::new(rules);
+    let fault_config = Arc::new(FaultConfig::new(
+        args.delay_ms,
+        args.drop_rate,
+        args.fault_code,
+        args.fault_rate,
+        args.seed,
+    ));
+    let tls_acceptor = if args.tls_self_signed || args.tls_cert.is_some() {
+        match tls::build_acceptor(args.tls_cert.as_deref(), args.tls_key.as_deref(), args.tls_self_signed) {
+            Ok(acceptor) => Some(acceptor),
+            Err(e) => panic!("Could not set up TLS: {e}"),
+        }
+    } else {
+        None
+    };
 
     let listener = match TcpListener::bind(&host_and_port) {
         Ok(listener) => {
@@ -36,16 +159,67 @@ fn main() {
             return;
         }
     };
-    for stream in listener.incoming() {
+    let pool = ThreadPool::new(args.workers);
+    // Assigned in accept order (single-threaded here) so --drop-rate/--fault-rate
+    // draws stay reproducible for a given --seed no matter how the worker pool
+    // schedules the resulting jobs.
+    for (connection_id, stream) in (0_u64..).zip(listener.incoming()) {
         let stream = stream.unwrap();
-        handle_connection(stream,http_response_code);
+        let peer_addr = stream.peer_addr().ok();
+        let default_response = Arc::clone(&default_response);
+        let rules = Arc::clone(&rules);
+        let fault_config = Arc::clone(&fault_config);
+        let record_path = args.record.clone();
+        match &tls_acceptor {
+            Some(acceptor) => {
+                let acceptor = Arc::clone(acceptor);
+                pool.execute(move || match acceptor.accept(stream) {
+                    Ok(tls_stream) => handle_connection(
+                        tls_stream,
+                        &default_response,
+                        &rules,
+                        &fault_config,
+                        connection_id,
+                        peer_addr,
+                        record_path.as_deref(),
+                    ),
+                    Err(e) => println!("TLS handshake failed: {}", e),
+                });
+            }
+            None => {
+                pool.execute(move || {
+                    handle_connection(
+                        stream,
+                        &default_response,
+                        &rules,
+                        &fault_config,
+                        connection_id,
+                        peer_addr,
+                        record_path.as_deref(),
+                    )
+                });
+            }
+        }
     }
 }
 
-fn handle_connection(mut stream: TcpStream, return_code :HttpStatusCode) {
+fn handle_connection<S: Read + Write>(
+    mut stream: S,
+    default_response: &DefaultResponse,
+    rules: &[Rule],
+    fault_config: &FaultConfig,
+    connection_id: u64,
+    peer_addr: Option<SocketAddr>,
+    record_path: Option<&Path>,
+) {
+    if fault_config.should_drop(connection_id) {
+        println!("Dropping connection per --drop-rate");
+        return;
+    }
     let mut buf_reader = BufReader::new(&mut stream);
     let mut headers = Vec::new();
     let mut line = String::new();
+    let mut request_line = None;
     loop {
         line.clear();
         match buf_reader.read_line(&mut line) {
@@ -54,7 +228,11 @@ fn handle_connection(mut stream: TcpStream, return_code :HttpStatusCode) {
                 if line == "\r\n" {
                     break;
                 }
-                headers.push(line.trim().to_string());
+                if request_line.is_none() {
+                    request_line = RequestLine::parse(&line);
+                } else {
+                    headers.push(line.trim().to_string());
+                }
             }
             Err(e) => {
                 println!("Error reading line: {}", e);
@@ -63,28 +241,34 @@ fn handle_connection(mut stream: TcpStream, return_code :HttpStatusCode) {
         }
     }
 
+    println!("Request line: {:?}", request_line);
     println!("Headers: {:#?}", headers);
 
+    let expects_continue = headers.iter().any(|header| {
+        header.to_lowercase().starts_with("expect:") && header.to_lowercase().contains("100-continue")
+    });
+    if expects_continue
+        && let Err(e) = buf_reader.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+    {
+        println!("Error writing 100 Continue: {}", e);
+        return;
+    }
+
     let is_chunked = headers.iter().any(|header| {
         header.to_lowercase().starts_with("transfer-encoding:") &&
             header.to_lowercase().contains("chunked")
     });
-    let mut body = String::new();
-    if is_chunked {
-        loop {
-            line.clear();
-            buf_reader.read_line(&mut line).unwrap();
-            let chunk_size_hex = line.trim();
-            let chunk_size = usize::from_str_radix(chunk_size_hex, 16).unwrap_or(0);
-
-            if chunk_size == 0 {
-                break;
+    let mut trailers = Vec::new();
+    let body = if is_chunked {
+        match chunked::read_chunked_body(&mut buf_reader) {
+            Ok(chunked_body) => {
+                trailers = chunked_body.trailers;
+                chunked_body.body
+            }
+            Err(e) => {
+                println!("Error reading chunked body: {}", e);
+                return;
             }
-
-            let mut chunk = vec![0; chunk_size];
-            buf_reader.read_exact(&mut chunk).unwrap();
-            body.push_str(&String::from_utf8_lossy(&chunk));
-            buf_reader.read_line(&mut line).unwrap();
         }
     } else {
         let content_length = headers
@@ -92,15 +276,125 @@ fn handle_connection(mut stream: TcpStream, return_code :HttpStatusCode) {
             .find(|header| header.to_lowercase().starts_with("content-length:"))
             .and_then(|header| header.split(": ").nth(1))
             .and_then(|value| value.trim().parse::<usize>().ok());
-        if let Some(length) = content_length {
-            let mut body_vec = vec![0; length];
-            buf_reader.read_exact(&mut body_vec).unwrap();
-            body = String::from_utf8(body_vec).unwrap();
+        match content_length {
+            Some(length) => {
+                let mut body_vec = vec![0; length];
+                if let Err(e) = buf_reader.read_exact(&mut body_vec) {
+                    println!("Error reading body: {}", e);
+                    return;
+                }
+                match String::from_utf8(body_vec) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        println!("Error decoding body as UTF-8: {}", e);
+                        return;
+                    }
+                }
+            }
+            None => String::new(),
         }
-    }
+    };
     println!("Body: {}", body);
-    let response = format!("HTTP/1.1 {} {}\r\n\r\n", return_code as i32, return_code.reason_phrase());
-    stream.write_all(response.as_bytes()).unwrap();
+    if !trailers.is_empty() {
+        println!("Trailers: {:#?}", trailers);
+    }
+
+    if let Some(record_path) = record_path {
+        let record = serde_json::json!({
+            "method": request_line.as_ref().map(|r| r.method.clone()),
+            "target": request_line.as_ref().map(|r| r.target.clone()),
+            "version": request_line.as_ref().map(|r| r.version.clone()),
+            "headers": headers_to_map(&headers),
+            "body": body,
+            "timestamp_ms": unix_timestamp_millis(),
+            "peer_addr": peer_addr.map(|addr| addr.to_string()),
+        });
+        if let Err(e) = record::append_record(record_path, &record) {
+            println!("Error recording request to {record_path:?}: {}", e);
+        }
+    }
+
+    if fault_config.delay_ms > 0 {
+        thread::sleep(Duration::from_millis(fault_config.delay_ms));
+    }
+
+    let response = match fault_config.fault_code(connection_id) {
+        Some(code) => build_response(code, reason_phrase_for(code), std::iter::empty(), b""),
+        None => {
+            let matched_rule = request_line
+                .as_ref()
+                .and_then(|request_line| rules::find_match(rules, request_line, &headers));
+            match matched_rule {
+                Some(rule) => build_response(
+                    rule.status,
+                    reason_phrase_for(rule.status),
+                    rule.response_headers.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+                    rule.body.as_bytes(),
+                ),
+                None => build_response(
+                    default_response.status as u16,
+                    default_response.status.reason_phrase(),
+                    default_response
+                        .headers
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.as_str())),
+                    &default_response.body,
+                ),
+            }
+        }
+    };
+    if let Err(e) = stream.write_all(&response) {
+        println!("Error writing response: {}", e);
+    }
+}
+
+/// Splits `"Name: value"` header lines into a name -> value map for the
+/// `--record` JSON log.
+fn headers_to_map(headers: &[String]) -> std::collections::HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|header| header.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Milliseconds since the Unix epoch, for the `--record` JSON log.
+fn unix_timestamp_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0)
+}
+
+/// Returns the reason phrase for a status code, or `""` if it isn't a code
+/// `waiter` knows about (rules may specify any code, not just the fixed set
+/// in [`HttpStatusCode`]).
+fn reason_phrase_for(code: u16) -> &'static str {
+    HttpStatusCode::from_u16(code)
+        .map(|code| code.reason_phrase())
+        .unwrap_or("")
+}
+
+/// Serializes a status line, headers and body into response bytes, adding a
+/// `Content-Length` computed from `body` unless one was already supplied.
+fn build_response<'a>(
+    status: u16,
+    reason: &str,
+    headers: impl Iterator<Item = (&'a str, &'a str)>,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {status} {reason}\r\n").into_bytes();
+    let mut has_content_length = false;
+    for (name, value) in headers {
+        has_content_length |= name.eq_ignore_ascii_case("content-length");
+        response.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+    }
+    if !has_content_length {
+        response.extend_from_slice(format!("Content-Length: {}\r\n", body.len()).as_bytes());
+    }
+    response.extend_from_slice(b"\r\n");
+    response.extend_from_slice(body);
+    response
 }
 #[derive(Debug, Clone, Copy)]
 pub enum HttpStatusCode {
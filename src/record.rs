@@ -0,0 +1,14 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Appends one JSON object per line to `path` (creating it if necessary),
+/// the capture analog of actix's request body inspection in its examples:
+/// a test can drive traffic at `waiter` then read this JSONL file back to
+/// assert exactly which requests arrived.
+pub fn append_record(path: &Path, record: &Value) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{record}")
+}
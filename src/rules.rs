@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+/// A single request line, as parsed off the wire: `GET /users/1?x=1 HTTP/1.1`.
+#[derive(Debug, Clone)]
+pub struct RequestLine {
+    pub method: String,
+    pub target: String,
+    pub version: String,
+}
+
+impl RequestLine {
+    /// Parses a raw request line. Returns `None` if it doesn't have the
+    /// `METHOD SP target SP version` shape.
+    pub fn parse(line: &str) -> Option<RequestLine> {
+        let mut parts = line.trim().splitn(3, ' ');
+        let method = parts.next()?.to_string();
+        let target = parts.next()?.to_string();
+        let version = parts.next()?.to_string();
+        Some(RequestLine {
+            method,
+            target,
+            version,
+        })
+    }
+}
+
+/// A rule as read from the rules file, before its `path` is compiled into a
+/// [`Rule`]. In `headers`, a `None` value means the header must be present
+/// with any value; `Some(v)` means it must be present and equal to `v`,
+/// ignoring ASCII case.
+#[derive(Debug, Deserialize)]
+struct RawRule {
+    method: Option<String>,
+    path: Option<String>,
+    #[serde(default)]
+    headers: HashMap<String, Option<String>>,
+    status: u16,
+    #[serde(default)]
+    response_headers: HashMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+/// A compiled matching rule: the first rule whose method/path/headers all
+/// match the incoming request dictates the response. Mirrors actix-web's
+/// `resource`/`Route::method`/`Route::filter` routing, but expressed as
+/// static config instead of code.
+#[derive(Debug)]
+pub struct Rule {
+    method: Option<String>,
+    path: Option<Regex>,
+    required_headers: HashMap<String, Option<String>>,
+    pub status: u16,
+    pub response_headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Rule {
+    fn from_raw(raw: RawRule) -> Result<Rule, String> {
+        let path = raw
+            .path
+            .map(|pattern| compile_path_pattern(&pattern))
+            .transpose()?;
+        Ok(Rule {
+            method: raw.method.map(|m| m.to_uppercase()),
+            path,
+            required_headers: raw.headers,
+            status: raw.status,
+            response_headers: raw.response_headers,
+            body: raw.body,
+        })
+    }
+
+    /// Returns `true` if this rule matches the given request line and headers.
+    pub fn matches(&self, request_line: &RequestLine, headers: &[String]) -> bool {
+        if let Some(method) = &self.method
+            && !method.eq_ignore_ascii_case(&request_line.method)
+        {
+            return false;
+        }
+        if let Some(path) = &self.path {
+            let target_path = request_line.target.split('?').next().unwrap_or("");
+            if !path.is_match(target_path) {
+                return false;
+            }
+        }
+        self.required_headers
+            .iter()
+            .all(|(name, expected)| header_matches(headers, name, expected.as_deref()))
+    }
+}
+
+fn header_matches(headers: &[String], name: &str, expected: Option<&str>) -> bool {
+    headers.iter().any(|header| {
+        let Some((header_name, header_value)) = header.split_once(':') else {
+            return false;
+        };
+        if !header_name.trim().eq_ignore_ascii_case(name) {
+            return false;
+        }
+        match expected {
+            Some(expected_value) => header_value.trim().eq_ignore_ascii_case(expected_value),
+            None => true,
+        }
+    })
+}
+
+/// Compiles a rule's `path` into a regex. A pattern that starts with `^` or
+/// ends with `$` is used as a regex exactly as written. Any other pattern is
+/// a glob like `/users/*`, anchored to the whole path: `.` is literal and `*`
+/// matches any run of characters; other characters keep their regex meaning.
+fn compile_path_pattern(pattern: &str) -> Result<Regex, String> {
+    let translated = if pattern.starts_with('^') || pattern.ends_with('$') {
+        pattern.to_string()
+    } else {
+        format!("^{}$", pattern.replace('.', "\\.").replace('*', ".*"))
+    };
+    Regex::new(&translated).map_err(|e| format!("invalid path pattern {pattern:?}: {e}"))
+}
+
+/// Loads rules from a JSON or TOML file (chosen by extension, JSON by
+/// default) into the order they should be tried in.
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("could not read {path:?}: {e}"))?;
+    let raw_rules: Vec<RawRule> = if path.extension().and_then(|e| e.to_str()) == Some("toml") {
+        #[derive(Deserialize)]
+        struct RulesFile {
+            #[serde(default)]
+            rules: Vec<RawRule>,
+        }
+        toml::from_str::<RulesFile>(&contents)
+            .map_err(|e| format!("invalid TOML in {path:?}: {e}"))?
+            .rules
+    } else {
+        serde_json::from_str(&contents).map_err(|e| format!("invalid JSON in {path:?}: {e}"))?
+    };
+    raw_rules.into_iter().map(Rule::from_raw).collect()
+}
+
+/// Finds the first rule that matches, if any.
+pub fn find_match<'a>(
+    rules: &'a [Rule],
+    request_line: &RequestLine,
+    headers: &[String],
+) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.matches(request_line, headers))
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+use openssl::x509::{X509, X509NameBuilder};
+
+/// Builds a TLS acceptor either from a PEM cert/key pair on disk, or (with
+/// `self_signed`) from a freshly generated, ephemeral self-signed
+/// certificate, mirroring the `ssl` setup actix-server/hyper examples do
+/// with openssl.
+pub fn build_acceptor(
+    cert_path: Option<&Path>,
+    key_path: Option<&Path>,
+    self_signed: bool,
+) -> Result<Arc<SslAcceptor>, String> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())
+        .map_err(|e| format!("could not create TLS acceptor: {e}"))?;
+
+    match (cert_path, key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            builder
+                .set_certificate_file(cert_path, SslFiletype::PEM)
+                .map_err(|e| format!("could not load TLS cert {cert_path:?}: {e}"))?;
+            builder
+                .set_private_key_file(key_path, SslFiletype::PEM)
+                .map_err(|e| format!("could not load TLS key {key_path:?}: {e}"))?;
+        }
+        (None, None) if self_signed => {
+            let (cert, key) = generate_self_signed()?;
+            builder
+                .set_certificate(&cert)
+                .map_err(|e| format!("could not install self-signed cert: {e}"))?;
+            builder
+                .set_private_key(&key)
+                .map_err(|e| format!("could not install self-signed key: {e}"))?;
+        }
+        _ => {
+            return Err(
+                "TLS mode requires either --tls-cert/--tls-key or --tls-self-signed".to_string(),
+            );
+        }
+    }
+
+    Ok(Arc::new(builder.build()))
+}
+
+fn generate_self_signed() -> Result<(X509, PKey<Private>), String> {
+    let rsa = Rsa::generate(2048).map_err(|e| format!("could not generate RSA key: {e}"))?;
+    let key = PKey::from_rsa(rsa).map_err(|e| format!("could not wrap RSA key: {e}"))?;
+
+    let mut name_builder = X509NameBuilder::new().map_err(|e| e.to_string())?;
+    name_builder
+        .append_entry_by_text("CN", "waiter.local")
+        .map_err(|e| e.to_string())?;
+    let name = name_builder.build();
+
+    let mut serial = BigNum::new().map_err(|e| e.to_string())?;
+    serial
+        .rand(159, MsbOption::MAYBE_ZERO, false)
+        .map_err(|e| e.to_string())?;
+
+    let mut builder = X509::builder().map_err(|e| e.to_string())?;
+    builder.set_version(2).map_err(|e| e.to_string())?;
+    builder
+        .set_serial_number(serial.to_asn1_integer().map_err(|e| e.to_string())?.as_ref())
+        .map_err(|e| e.to_string())?;
+    builder.set_subject_name(&name).map_err(|e| e.to_string())?;
+    builder.set_issuer_name(&name).map_err(|e| e.to_string())?;
+    builder.set_pubkey(&key).map_err(|e| e.to_string())?;
+    builder
+        .set_not_before(Asn1Time::days_from_now(0).map_err(|e| e.to_string())?.as_ref())
+        .map_err(|e| e.to_string())?;
+    builder
+        .set_not_after(Asn1Time::days_from_now(365).map_err(|e| e.to_string())?.as_ref())
+        .map_err(|e| e.to_string())?;
+    builder
+        .append_extension(
+            BasicConstraints::new()
+                .critical()
+                .build()
+                .map_err(|e| e.to_string())?,
+        )
+        .map_err(|e| e.to_string())?;
+    let subject_alt_name = {
+        let context = builder.x509v3_context(None, None);
+        SubjectAlternativeName::new()
+            .dns("localhost")
+            .ip("127.0.0.1")
+            .build(&context)
+            .map_err(|e| e.to_string())?
+    };
+    builder
+        .append_extension(subject_alt_name)
+        .map_err(|e| e.to_string())?;
+    builder
+        .sign(&key, MessageDigest::sha256())
+        .map_err(|e| e.to_string())?;
+
+    Ok((builder.build(), key))
+}